@@ -14,6 +14,7 @@ pub mod folder;
 pub mod from_flat;
 mod serialize;
 pub mod smtlib2;
+mod union_find;
 pub mod visitor;
 mod witness;
 
@@ -25,6 +26,7 @@ pub use crate::common::RuntimeError;
 pub use crate::common::Solver;
 pub use crate::common::Variable;
 
+pub use self::union_find::optimize as optimize_linear_equalities;
 pub use self::witness::Witness;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Derivative)]
@@ -200,6 +202,24 @@ impl<'ast, T> Prog<'ast, T> {
     }
 }
 
+impl<'ast, T: Field> Prog<'ast, T> {
+    /// Runs the weighted union-find pass (see `union_find`) that coalesces
+    /// variables tied together by a simple proportional constraint, then
+    /// discards the remapping table.
+    ///
+    /// This operates on this crate's `ir::Prog`. `zokrates_circom::write_r1cs`
+    /// exports a separate `zokrates_core::ir::Prog` that this crate has no
+    /// conversion into, so nothing in this workspace calls this yet and it
+    /// has no effect on `write_r1cs`'s `n_wires`/`n_constraints` today.
+    /// Wiring it into that export path would also need a renumbering pass
+    /// first: `write_r1cs`'s wire ids are raw variable ids with no
+    /// compaction, so a merge that drops a variable would leave a hole in
+    /// the id space and corrupt the `.r1cs`/`.wtns` pair.
+    pub fn propagate_linear_equalities(self) -> Self {
+        optimize_linear_equalities(self).0
+    }
+}
+
 impl<'ast, T: Field> fmt::Display for Prog<'ast, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let returns = (0..self.return_count)
@@ -0,0 +1,377 @@
+use super::{Directive, LinComb, Prog, PublicInputs, QuadComb, Statement, Variable};
+use std::collections::HashMap;
+use zokrates_field::Field;
+
+/// A weighted union-find (disjoint-set) over `Variable`s, used by `optimize`
+/// (below) to coalesce variables related by a simple proportional
+/// constraint `x = k * y` within this crate's `ir::Prog`.
+///
+/// Each class is represented by a root variable. For any non-root member `x`
+/// of a class, `ratio[x]` gives the coefficient `k` such that `x = k * root`
+/// once `find` has been applied (path compression folds the ratios along the
+/// path into a single coefficient relative to the root).
+struct UnionFind<T> {
+    index: HashMap<Variable, usize>,
+    variables: Vec<Variable>,
+    // negative at `i`: `i` is a root, magnitude is the size of its class
+    // non-negative at `i`: index of `i`'s parent
+    parent: Vec<isize>,
+    // coefficient `k` such that `variables[i] = k * variables[parent[i]]`
+    ratio: Vec<T>,
+}
+
+impl<T: Field> UnionFind<T> {
+    fn new() -> Self {
+        UnionFind {
+            index: HashMap::new(),
+            variables: Vec::new(),
+            parent: Vec::new(),
+            ratio: Vec::new(),
+        }
+    }
+
+    fn entry(&mut self, v: Variable) -> usize {
+        if let Some(&i) = self.index.get(&v) {
+            return i;
+        }
+        let i = self.variables.len();
+        self.index.insert(v, i);
+        self.variables.push(v);
+        self.parent.push(-1);
+        self.ratio.push(T::one());
+        i
+    }
+
+    /// Returns the root of `i`'s class and the ratio `k` such that
+    /// `variables[i] = k * variables[root]`, compressing the path as it goes.
+    fn find(&mut self, i: usize) -> (usize, T) {
+        if self.parent[i] < 0 {
+            return (i, T::one());
+        }
+        let (root, parent_ratio) = self.find(self.parent[i] as usize);
+        let ratio = self.ratio[i].clone() * parent_ratio;
+        self.parent[i] = root as isize;
+        self.ratio[i] = ratio.clone();
+        (root, ratio)
+    }
+
+    fn size(&self, root: usize) -> isize {
+        -self.parent[root]
+    }
+
+    /// Attempts to merge the classes of `x` and `y` knowing that `a * x == b
+    /// * y`. Returns `false`, leaving every class as it was, when the
+    /// merge is refused: it would move the constant `~one` wire, it would
+    /// unify two distinct public variables, or the ratio would require
+    /// dividing by a coefficient of zero (no inverse exists for it).
+    ///
+    /// A public variable is never demoted to a non-root: doing so would let
+    /// `rewrite_lincomb` replace every occurrence of it with `ratio *
+    /// private_root`, erasing the public wire from the exported R1CS and
+    /// leaving it unconstrained. So a merge touching exactly one public
+    /// variable always keeps that variable as the root, regardless of the
+    /// union-by-size tree sizes.
+    fn union(&mut self, x: Variable, a: T, y: Variable, b: T, public: &PublicInputs) -> bool {
+        if x == Variable::one() || y == Variable::one() || a == T::zero() || b == T::zero() {
+            return false;
+        }
+
+        let ix = self.entry(x);
+        let iy = self.entry(y);
+        let (rx, rx_ratio) = self.find(ix);
+        let (ry, ry_ratio) = self.find(iy);
+
+        if rx == ry {
+            return true;
+        }
+
+        let rx_public = public.contains(&self.variables[rx]);
+        let ry_public = public.contains(&self.variables[ry]);
+
+        if rx_public && ry_public {
+            return false;
+        }
+
+        // x = (b/a) * y. Substituting x = rx_ratio * variables[rx] and
+        // y = ry_ratio * variables[ry] and solving for `variables[rx]` in
+        // terms of `variables[ry]` gives the ratio to use when `ry` becomes
+        // the root: `variables[rx] = k * variables[ry]` with
+        // `k = (b * ry_ratio) / (a * rx_ratio)`. When `rx` becomes the root
+        // instead, the same relation rearranges to `variables[ry] = 1/k *
+        // variables[rx]`. `a`, `rx_ratio` and `ry_ratio` are all non-zero.
+        let k = b * ry_ratio / (a * rx_ratio);
+
+        let new_size = self.size(rx) + self.size(ry);
+
+        // keep a public variable as the root unconditionally; only fall back
+        // to union-by-size when neither side is public
+        let rx_becomes_root = rx_public || (!ry_public && self.size(rx) >= self.size(ry));
+
+        if rx_becomes_root {
+            self.parent[ry] = rx as isize;
+            self.ratio[ry] = T::one() / k;
+            self.parent[rx] = -new_size;
+        } else {
+            self.parent[rx] = ry as isize;
+            self.ratio[rx] = k;
+            self.parent[ry] = -new_size;
+        }
+
+        true
+    }
+}
+
+/// If `l` reduces to a single summand `a * x` (with `x` the only variable
+/// carrying a non-zero coefficient), returns `(x, a)`.
+fn as_single_term<T: Field>(l: &LinComb<T>) -> Option<(Variable, T)> {
+    let mut terms = l.clone().into_canonical().0.into_iter();
+    match (terms.next(), terms.next()) {
+        (Some((v, a)), None) => Some((v, a)),
+        _ => None,
+    }
+}
+
+/// If `q` is purely linear, ie one side of the product is the constant `1`,
+/// returns the other side, the effective linear combination being compared
+/// to the constraint's right hand side.
+fn as_linear<T: Field>(q: &QuadComb<T>) -> Option<LinComb<T>> {
+    if q.left == LinComb::one() {
+        Some(q.right.clone())
+    } else if q.right == LinComb::one() {
+        Some(q.left.clone())
+    } else {
+        None
+    }
+}
+
+fn rewrite_lincomb<T: Field>(l: &LinComb<T>, uf: &mut UnionFind<T>) -> LinComb<T> {
+    LinComb(
+        l.0.iter()
+            .map(|(v, coeff)| {
+                let i = uf.entry(*v);
+                let (root, ratio) = uf.find(i);
+                (uf.variables[root], coeff.clone() * ratio)
+            })
+            .collect(),
+    )
+}
+
+fn rewrite_quadcomb<T: Field>(q: &QuadComb<T>, uf: &mut UnionFind<T>) -> QuadComb<T> {
+    QuadComb {
+        left: rewrite_lincomb(&q.left, uf),
+        right: rewrite_lincomb(&q.right, uf),
+    }
+}
+
+/// Collapses variables tied together by simple proportional constraints
+/// (`a * x == b * y`) into a single representative, shrinking the number of
+/// wires and constraints in this `ir::Prog`.
+///
+/// Returns the rewritten program along with a remapping table mapping every
+/// merged variable to the representative it was folded into and the ratio
+/// relating them (`variable = ratio * representative`).
+///
+/// Note: this crate's `ir::Prog` is not the type `zokrates_circom::write_r1cs`
+/// exports (see `Prog::propagate_linear_equalities`), so calling this alone
+/// does not currently shrink a `write_r1cs` output's `n_wires`/`n_constraints`.
+pub fn optimize<'ast, T: Field>(p: Prog<'ast, T>) -> (Prog<'ast, T>, HashMap<Variable, (Variable, T)>) {
+    let public: PublicInputs = p.public_inputs().union(&p.returns().into_iter().collect()).cloned().collect();
+
+    let mut uf = UnionFind::new();
+
+    for s in &p.statements {
+        if let Statement::Constraint(quad, lin, _) = s {
+            if let (Some(left), Some((y, b))) = (as_linear(quad), as_single_term(lin)) {
+                if let Some((x, a)) = as_single_term(&left) {
+                    uf.union(x, a, y, b, &public);
+                }
+            }
+        }
+    }
+
+    let statements = p
+        .statements
+        .into_iter()
+        .filter_map(|s| match s {
+            Statement::Constraint(quad, lin, error) => {
+                let quad = rewrite_quadcomb(&quad, &mut uf);
+                let lin = rewrite_lincomb(&lin, &mut uf);
+                // a constraint that became `k * r == k * r` after rewriting
+                // is now trivially true and can be dropped
+                match (as_linear(&quad), as_single_term(&lin)) {
+                    (Some(left), Some((y, b))) => match as_single_term(&left) {
+                        Some((x, a)) if x == y && a == b => None,
+                        _ => Some(Statement::Constraint(quad, lin, error)),
+                    },
+                    _ => Some(Statement::Constraint(quad, lin, error)),
+                }
+            }
+            Statement::Directive(d) => Some(Statement::Directive(Directive {
+                inputs: d
+                    .inputs
+                    .iter()
+                    .map(|q| rewrite_quadcomb(q, &mut uf))
+                    .collect(),
+                outputs: d.outputs,
+                solver: d.solver,
+            })),
+            Statement::Log(format, expressions) => Some(Statement::Log(
+                format,
+                expressions
+                    .into_iter()
+                    .map(|(ty, lincombs)| {
+                        (
+                            ty,
+                            lincombs.iter().map(|l| rewrite_lincomb(l, &mut uf)).collect(),
+                        )
+                    })
+                    .collect(),
+            )),
+            s => Some(s),
+        })
+        .collect();
+
+    let map = uf
+        .variables
+        .iter()
+        .enumerate()
+        .filter_map(|(i, v)| {
+            if uf.parent[i] < 0 {
+                None
+            } else {
+                let (root, ratio) = uf.find(i);
+                Some((*v, (uf.variables[root], ratio)))
+            }
+        })
+        .collect();
+
+    (
+        Prog {
+            arguments: p.arguments,
+            return_count: p.return_count,
+            statements,
+        },
+        map,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Parameter;
+    use zokrates_field::Bn128Field;
+
+    #[test]
+    fn coalesces_two_private_variables() {
+        let x = Variable::new(1);
+        let y = Variable::new(2);
+
+        let mut uf: UnionFind<Bn128Field> = UnionFind::new();
+        // 2 * x == 4 * y, ie x == 2 * y
+        assert!(uf.union(x, Bn128Field::from(2), y, Bn128Field::from(4), &PublicInputs::new()));
+
+        let ix = uf.entry(x);
+        let iy = uf.entry(y);
+        let (rx, rx_ratio) = uf.find(ix);
+        let (ry, ry_ratio) = uf.find(iy);
+
+        assert_eq!(rx, ry);
+        // whichever variable ended up as the root, the other one's ratio
+        // must still relate them as `x == 2 * y`
+        if rx == ix {
+            assert_eq!(ry_ratio, Bn128Field::from(2));
+        } else {
+            assert_eq!(rx_ratio * Bn128Field::from(2), Bn128Field::from(1));
+        }
+    }
+
+    #[test]
+    fn refuses_to_merge_two_distinct_public_variables() {
+        let x = Variable::public(0);
+        let y = Variable::public(1);
+        let public: PublicInputs = vec![x, y].into_iter().collect();
+
+        let mut uf: UnionFind<Bn128Field> = UnionFind::new();
+        assert!(!uf.union(x, Bn128Field::from(1), y, Bn128Field::from(1), &public));
+    }
+
+    #[test]
+    fn never_demotes_a_public_variable_to_non_root() {
+        let public_var = Variable::public(0);
+        let private_var = Variable::new(1);
+        let public: PublicInputs = vec![public_var].into_iter().collect();
+
+        let mut uf: UnionFind<Bn128Field> = UnionFind::new();
+
+        // grow the private variable's class first so it would win a plain
+        // union-by-size tie-break against the still-singleton public one
+        let other_private = Variable::new(2);
+        assert!(uf.union(
+            private_var,
+            Bn128Field::from(1),
+            other_private,
+            Bn128Field::from(1),
+            &public
+        ));
+
+        assert!(uf.union(
+            public_var,
+            Bn128Field::from(1),
+            private_var,
+            Bn128Field::from(1),
+            &public
+        ));
+
+        let i = uf.entry(public_var);
+        let (root, _) = uf.find(i);
+        assert_eq!(uf.variables[root], public_var);
+    }
+
+    #[test]
+    fn refuses_a_merge_requiring_division_by_zero() {
+        let x = Variable::new(1);
+        let y = Variable::new(2);
+
+        let mut uf: UnionFind<Bn128Field> = UnionFind::new();
+        assert!(!uf.union(x, Bn128Field::from(0), y, Bn128Field::from(1), &PublicInputs::new()));
+    }
+
+    #[test]
+    fn never_touches_the_constant_one_wire() {
+        let one = Variable::one();
+        let y = Variable::new(1);
+
+        let mut uf: UnionFind<Bn128Field> = UnionFind::new();
+        assert!(!uf.union(one, Bn128Field::from(1), y, Bn128Field::from(1), &PublicInputs::new()));
+    }
+
+    #[test]
+    fn optimize_coalesces_a_linear_equality_and_drops_the_trivial_constraint() {
+        // def main(private field a) -> (field):
+        //     field b = a        // b := a, a simple proportional constraint
+        //     return b
+        let a = Variable::new(1);
+        let b = Variable::new(2);
+
+        let prog: Prog<Bn128Field> = Prog {
+            arguments: vec![Parameter {
+                id: a,
+                private: true,
+            }],
+            return_count: 1,
+            statements: vec![
+                Statement::definition(b, LinComb::from(a)),
+                Statement::definition(Variable::public(0), LinComb::from(b)),
+            ],
+        };
+
+        let (optimized, map) = optimize(prog);
+
+        // `b` was folded into `a`'s class
+        assert!(map.contains_key(&b));
+        // the output's constraint is proportional too, so `a`, `b` and the
+        // (public, hence root) output end up in one class: both constraints
+        // become trivial and are dropped
+        assert_eq!(optimized.statements.len(), 0);
+    }
+}
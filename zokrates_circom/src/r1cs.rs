@@ -1,10 +1,12 @@
 use byteorder::{LittleEndian, WriteBytesExt};
-use std::collections::BTreeSet;
-use std::io::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Result, Seek, SeekFrom};
 use std::{io::Write, ops::Add};
 use zokrates_core::flat_absy::FlatVariable;
-use zokrates_core::ir::{LinComb, Prog, Statement};
+use zokrates_core::ir::{LinComb, Prog, ProgIterator, Statement};
 use zokrates_field::Field;
+
+pub use zokrates_core::ir::Witness;
 struct Header {
     pub field_size: u32,
     pub prime_size: Vec<u8>,
@@ -29,33 +31,71 @@ fn write_header<W: Write>(writer: &mut W, header: Header) -> Result<()> {
     Ok(())
 }
 
-pub fn write_r1cs<T: Field, W: Write>(writer: &mut W, p: Prog<T>) -> Result<()> {
+/// Writes `p` as an `.r1cs` file. See [`write_r1cs_with_map`] for the full
+/// behaviour; this entry point is for callers that do not need the
+/// wire-to-label map back.
+pub fn write_r1cs<T: Field, I: IntoIterator<Item = Statement<T>>, W: Write>(
+    writer: &mut W,
+    p: ProgIterator<T, I>,
+) -> Result<()> {
+    write_r1cs_with_map(writer, p).map(|_| ())
+}
+
+/// Writes `p` as an `.r1cs` file, consuming its statements exactly once so
+/// that `p.statements` never needs to be fully materialized in memory: a
+/// single pass builds the wire set and the running totals it depends on
+/// (`n_wires`, `n_constraints`, the constraints section's own byte size)
+/// while spilling the serialized constraint rows to a side buffer, since
+/// `writer` isn't assumed to be seekable. Once the pass is done, the section
+/// size and the buffered constraints are written out, followed by the header
+/// and wire2label sections.
+/// See [`write_r1cs_with_map_seek`] for a variant that streams constraints
+/// straight to `writer` instead of buffering them, for callers that do have
+/// a seekable sink.
+///
+/// Also builds the wire2label signal map: for every emitted wire, the
+/// original `FlatVariable` identity it came from survives the
+/// `shift_variable` renumbering, encoded into the label space written to the
+/// wire2label section. The same map is returned as `(wire, label)` pairs so
+/// callers can correlate prover wires back to ZoKrates IR variables, e.g. for
+/// debugging or witness-value inspection.
+pub fn write_r1cs_with_map<T: Field, I: IntoIterator<Item = Statement<T>>, W: Write>(
+    writer: &mut W,
+    p: ProgIterator<T, I>,
+) -> Result<Vec<(u32, u64)>> {
     let modulo_byte_count = T::max_value().to_biguint().add(1u32).to_bytes_le().len() as u32;
 
     let n_pub_out = p.return_count as u32;
     let n_pub_in = p.arguments.iter().filter(|a| !a.private).count() as u32;
+    let n_prv_in = p.arguments.iter().filter(|a| a.private).count() as u32;
 
     let shift = n_pub_out + n_pub_in;
 
-    let wires = p.statements.iter().fold(
-        vec![0u32].into_iter().collect::<BTreeSet<_>>(),
-        |mut acc: BTreeSet<u32>, s| match s {
-            Statement::Constraint(q, l, _) => {
-                acc.extend(
-                    q.left
-                        .0
-                        .iter()
-                        .chain(q.right.0.iter())
-                        .chain(l.0.iter())
-                        .map(|(v, _)| shift_variable(v, shift)),
-                );
-                acc
+    // wire (post-`shift_variable`) -> label (the source `FlatVariable`'s
+    // signed id, encoded into the label space)
+    let mut wire2label = BTreeMap::new();
+    wire2label.insert(0u32, 0u64);
+    let mut n_constraints = 0u32;
+
+    // spilled here rather than written straight to `writer`, since the
+    // section's size has to be written before its data and `writer` may not
+    // be seekable
+    let mut constraints = Vec::new();
+    for s in p.statements {
+        if let Statement::Constraint(q, l, _) = &s {
+            for (v, _) in q.left.0.iter().chain(q.right.0.iter()).chain(l.0.iter()) {
+                wire2label.insert(shift_variable(v, shift), label_variable(v));
             }
-            _ => acc,
-        },
-    );
+            n_constraints += 1;
+        }
+        write_statement(&mut constraints, &s, shift)?;
+    }
 
-    let n_wires = wires.len();
+    let n_wires = wire2label.len();
+    // the true count of distinct source signals, as opposed to wires: these
+    // coincide today since nothing merges wire ids yet, but would diverge
+    // once something like `ir::Prog::propagate_linear_equalities` is wired in
+    let n_labels = wire2label.values().collect::<BTreeSet<_>>().len() as u64;
 
     let header = Header {
         field_size: modulo_byte_count,
@@ -63,12 +103,64 @@ pub fn write_r1cs<T: Field, W: Write>(writer: &mut W, p: Prog<T>) -> Result<()>
         n_wires: n_wires as u32,
         n_pub_out,
         n_pub_in,
-        n_prv_in: p.arguments.iter().filter(|a| a.private).count() as u32,
-        n_labels: n_wires as u64,
-        n_constraints: p.constraint_count() as u32,
+        n_prv_in,
+        n_labels,
+        n_constraints,
     };
 
-    let shift = header.n_pub_out + header.n_pub_in;
+    // magic
+    writer.write(&[0x72, 0x31, 0x63, 0x73])?;
+    // version
+    writer.write_u32::<LittleEndian>(1)?;
+    // section count
+    writer.write_u32::<LittleEndian>(3)?;
+
+    // section type: constraints
+    // type
+    writer.write_u32::<LittleEndian>(2)?;
+    // size
+    writer.write_u64::<LittleEndian>(constraints.len() as u64)?;
+    writer.write(&constraints)?;
+
+    // section type: header
+    // type
+    writer.write_u32::<LittleEndian>(1)?;
+    // size
+    writer.write_u64::<LittleEndian>(32 + 32)?;
+
+    // header
+    write_header(writer, header)?;
+
+    // section type: wire2label
+    // type
+    writer.write_u32::<LittleEndian>(3)?;
+    // size
+    writer.write_u64::<LittleEndian>(n_wires as u64 * 8)?;
+
+    write_table(writer, &wire2label)?;
+
+    Ok(wire2label.into_iter().collect())
+}
+
+/// Streaming variant of [`write_r1cs_with_map`] for writers that are
+/// [`Seek`]: instead of spilling the constraints section to a side buffer,
+/// it writes constraint rows straight to `writer` and backpatches the
+/// section size by seeking once the single pass over `p.statements` is
+/// done; the header and wire2label sections are then written after the
+/// constraints. Prefer this over
+/// [`write_r1cs_with_map`] when `writer` is a file or another large sink
+/// where buffering the whole constraints section isn't desirable.
+pub fn write_r1cs_with_map_seek<T: Field, I: IntoIterator<Item = Statement<T>>, W: Write + Seek>(
+    writer: &mut W,
+    p: ProgIterator<T, I>,
+) -> Result<Vec<(u32, u64)>> {
+    let modulo_byte_count = T::max_value().to_biguint().add(1u32).to_bytes_le().len() as u32;
+
+    let n_pub_out = p.return_count as u32;
+    let n_pub_in = p.arguments.iter().filter(|a| !a.private).count() as u32;
+    let n_prv_in = p.arguments.iter().filter(|a| a.private).count() as u32;
+
+    let shift = n_pub_out + n_pub_in;
 
     // magic
     writer.write(&[0x72, 0x31, 0x63, 0x73])?;
@@ -80,23 +172,49 @@ pub fn write_r1cs<T: Field, W: Write>(writer: &mut W, p: Prog<T>) -> Result<()>
     // section type: constraints
     // type
     writer.write_u32::<LittleEndian>(2)?;
-    // size: 4 per lc + (32 + 4) per summand
-    let size = p
-        .statements
-        .iter()
-        .map(|s| match s {
-            Statement::Constraint(q, l, _) => {
-                (3 * 4 // for each lc, 4 bytes for its size
-                    + (q.left.0.len() + q.right.0.len() + l.0.len()) // for each summand
-                        * (modulo_byte_count as usize + 4)) // 4 bytes for the signal, `modulo_byte_count` bytes for the coefficient
-                as u64
+    // size: backpatched below once the single pass over `p.statements` is done
+    let size_position = writer.stream_position()?;
+    writer.write_u64::<LittleEndian>(0)?;
+
+    // wire (post-`shift_variable`) -> label (the source `FlatVariable`'s
+    // signed id, encoded into the label space)
+    let mut wire2label = BTreeMap::new();
+    wire2label.insert(0u32, 0u64);
+    let mut n_constraints = 0u32;
+
+    let constraints_start = writer.stream_position()?;
+    for s in p.statements {
+        if let Statement::Constraint(q, l, _) = &s {
+            for (v, _) in q.left.0.iter().chain(q.right.0.iter()).chain(l.0.iter()) {
+                wire2label.insert(shift_variable(v, shift), label_variable(v));
             }
-            _ => 0,
-        })
-        .sum();
+            n_constraints += 1;
+        }
+        write_statement(writer, &s, shift)?;
+    }
+    let constraints_end = writer.stream_position()?;
+    let size = constraints_end - constraints_start;
+
+    writer.seek(SeekFrom::Start(size_position))?;
     writer.write_u64::<LittleEndian>(size)?;
+    writer.seek(SeekFrom::Start(constraints_end))?;
 
-    write_constraints(writer, &p, shift)?;
+    let n_wires = wire2label.len();
+    // the true count of distinct source signals, as opposed to wires: these
+    // coincide today since nothing merges wire ids yet, but would diverge
+    // once something like `ir::Prog::propagate_linear_equalities` is wired in
+    let n_labels = wire2label.values().collect::<BTreeSet<_>>().len() as u64;
+
+    let header = Header {
+        field_size: modulo_byte_count,
+        prime_size: T::max_value().to_biguint().add(1u32).to_bytes_le(),
+        n_wires: n_wires as u32,
+        n_pub_out,
+        n_pub_in,
+        n_prv_in,
+        n_labels,
+        n_constraints,
+    };
 
     // section type: header
     // type
@@ -113,16 +231,16 @@ pub fn write_r1cs<T: Field, W: Write>(writer: &mut W, p: Prog<T>) -> Result<()>
     // size
     writer.write_u64::<LittleEndian>(n_wires as u64 * 8)?;
 
-    write_table(writer, &wires)?;
+    write_table(writer, &wire2label)?;
 
-    Ok(())
+    Ok(wire2label.into_iter().collect())
 }
 
-fn write_constraints<T: Field, W: Write>(writer: &mut W, p: &Prog<T>, shift: u32) -> Result<()> {
-    for s in &p.statements {
-        write_statement(writer, s, shift)?;
-    }
-    Ok(())
+/// Encodes a `FlatVariable`'s signed id into the unsigned label space used
+/// by the wire2label section, preserving its identity through
+/// `shift_variable`'s renumbering.
+fn label_variable(var: &FlatVariable) -> u64 {
+    var.id as i64 as u64
 }
 
 fn write_statement<T: Field, W: Write>(writer: &mut W, s: &Statement<T>, shift: u32) -> Result<()> {
@@ -168,16 +286,89 @@ fn write_lincomb<T: Field, W: Write>(writer: &mut W, l: &LinComb<T>, shift: u32)
     Ok(())
 }
 
-// for now we do not write any signal map
-fn write_table<W: Write>(w: &mut W, variables: &BTreeSet<u32>) -> Result<()> {
-    for v in variables {
-        w.write_u64::<LittleEndian>(*v as u64)?;
+fn write_table<W: Write>(w: &mut W, wire2label: &BTreeMap<u32, u64>) -> Result<()> {
+    for label in wire2label.values() {
+        w.write_u64::<LittleEndian>(*label)?;
     }
     Ok(())
 }
 
+/// Writes the `.wtns` companion of the `.r1cs` file produced by
+/// [`write_r1cs`]. The witness values are laid out in exactly the wire order
+/// of `write_r1cs`'s wire2label table -- the constant `~one`, then public
+/// outputs, then public inputs, then the private/internal assignments -- so
+/// that a downstream prover can zip the two files together. Like
+/// `write_r1cs_with_map`, a declared argument (public or private) that is
+/// never referenced by a constraint is dropped rather than given a wire.
+pub fn write_wtns<T: Field, W: Write>(writer: &mut W, p: &Prog<T>, witness: &Witness<T>) -> Result<()> {
+    let modulo_byte_count = T::max_value().to_biguint().add(1u32).to_bytes_le().len() as u32;
+
+    // only a variable that is actually referenced by a constraint becomes a
+    // wire in `write_r1cs`'s output (see `write_r1cs_with_map`); a witness
+    // value for a variable that's never referenced -- a directive-only
+    // private variable, or a declared-but-unused public argument -- must be
+    // excluded here too, or the wire counts of the `.r1cs`/`.wtns` pair
+    // would disagree
+    let mut constrained_ids = BTreeSet::new();
+    for s in &p.statements {
+        if let Statement::Constraint(q, l, _) = s {
+            for (v, _) in q.left.0.iter().chain(q.right.0.iter()).chain(l.0.iter()) {
+                if v.id != 0 {
+                    constrained_ids.insert(v.id);
+                }
+            }
+        }
+    }
+
+    let mut private: Vec<_> = witness
+        .0
+        .iter()
+        .filter(|(v, _)| v.id > 0 && constrained_ids.contains(&v.id))
+        .collect();
+    private.sort_by_key(|(v, _)| v.id);
+
+    let values: Vec<T> = std::iter::once(T::one())
+        .chain(witness.return_values())
+        .chain(
+            p.arguments
+                .iter()
+                .filter(|a| !a.private && constrained_ids.contains(&a.id.id))
+                .map(|a| witness.0.get(&a.id).unwrap().clone()),
+        )
+        .chain(private.into_iter().map(|(_, value)| value.clone()))
+        .collect();
+
+    // magic
+    writer.write(b"wtns")?;
+    // version
+    writer.write_u32::<LittleEndian>(2)?;
+    // section count
+    writer.write_u32::<LittleEndian>(2)?;
+
+    // section type: header
+    writer.write_u32::<LittleEndian>(1)?;
+    writer.write_u64::<LittleEndian>(4 + modulo_byte_count as u64 + 8)?;
+    writer.write_u32::<LittleEndian>(modulo_byte_count)?;
+    writer.write(&T::max_value().to_biguint().add(1u32).to_bytes_le())?;
+    writer.write_u64::<LittleEndian>(values.len() as u64)?;
+
+    // section type: data
+    writer.write_u32::<LittleEndian>(2)?;
+    writer.write_u64::<LittleEndian>(values.len() as u64 * modulo_byte_count as u64)?;
+    for v in &values {
+        let mut res = vec![0u8; modulo_byte_count as usize];
+        for (value, padded) in v.to_biguint().to_bytes_le().iter().zip(res.iter_mut()) {
+            *padded = *value;
+        }
+        writer.write(&res)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::io::Cursor;
 
     use super::*;
@@ -185,7 +376,7 @@ mod tests {
     use zkutil::r1cs_reader;
     use zokrates_core::{
         flat_absy::FlatVariable,
-        ir::{LinComb, Statement},
+        ir::{LinComb, Parameter, Statement},
     };
     use zokrates_field::Bn128Field;
 
@@ -277,10 +468,11 @@ mod tests {
             0x00, 0x00, 0x00, 0x00, 
             0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 
             0x01, 0x00, 0x00, 0x00,
-            // wire map (one, pub0)
+            // wire map (one, pub0) -- labels: ~one is signal 0, pub0 is the
+            // `FlatVariable::public(0)` id (-1) encoded into the label space
             0x03, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 
-            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
         ];
 
         write_r1cs(&mut buf, prog).unwrap();
@@ -291,4 +483,257 @@ mod tests {
 
         assert!(r1cs_reader::read(c).is_ok());
     }
+
+    #[test]
+    fn empty_wtns() {
+        let prog: Prog<Bn128Field> = Prog::default();
+        let witness: Witness<Bn128Field> = Witness(BTreeMap::new());
+
+        let mut buf = Vec::new();
+
+        #[rustfmt::skip]
+        let expected: Vec<u8> = vec![
+            // magic
+            0x77, 0x74, 0x6e, 0x73,
+            // version
+            0x02, 0x00, 0x00, 0x00,
+            // section count
+            0x02, 0x00, 0x00, 0x00,
+            // header section
+            0x01, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // field size in bytes
+            0x20, 0x00, 0x00, 0x00,
+            // modulus
+            0x01, 0x00, 0x00, 0xf0, 0x93, 0xf5, 0xe1, 0x43, 0x91, 0x70, 0xb9, 0x79, 0x48, 0xe8, 0x33, 0x28, 0x5d, 0x58, 0x81, 0x81, 0xb6, 0x45, 0x50, 0xb8, 0x29, 0xa0, 0x31, 0xe1, 0x72, 0x4e, 0x64, 0x30,
+            // witness length
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // data section: the `~one` value
+            0x02, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        write_wtns(&mut buf, &prog, &witness).unwrap();
+
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn return_one_wtns() {
+        let prog: Prog<Bn128Field> = Prog {
+            arguments: vec![],
+            return_count: 1,
+            statements: vec![Statement::Constraint(
+                LinComb::one().into(),
+                FlatVariable::public(0).into(),
+                None,
+            )],
+        };
+
+        let witness: Witness<Bn128Field> = Witness(
+            vec![(FlatVariable::public(0), Bn128Field::from(3))]
+                .into_iter()
+                .collect(),
+        );
+
+        let mut buf = Vec::new();
+
+        #[rustfmt::skip]
+        let expected: Vec<u8> = vec![
+            0x77, 0x74, 0x6e, 0x73,
+            0x02, 0x00, 0x00, 0x00,
+            0x02, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x20, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0xf0, 0x93, 0xf5, 0xe1, 0x43, 0x91, 0x70, 0xb9, 0x79, 0x48, 0xe8, 0x33, 0x28, 0x5d, 0x58, 0x81, 0x81, 0xb6, 0x45, 0x50, 0xb8, 0x29, 0xa0, 0x31, 0xe1, 0x72, 0x4e, 0x64, 0x30,
+            // witness length: one + pub0
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // data section
+            0x02, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // ~one
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // pub0 (return value)
+            0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        write_wtns(&mut buf, &prog, &witness).unwrap();
+
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn seek_variant_matches_the_buffering_one() {
+        fn return_one_prog() -> Prog<Bn128Field> {
+            Prog {
+                arguments: vec![],
+                return_count: 1,
+                statements: vec![Statement::Constraint(
+                    LinComb::one().into(),
+                    FlatVariable::public(0).into(),
+                    None,
+                )],
+            }
+        }
+
+        let mut buffered = Vec::new();
+        write_r1cs(&mut buffered, return_one_prog()).unwrap();
+
+        let mut seeked = Cursor::new(Vec::new());
+        write_r1cs_with_map_seek(&mut seeked, return_one_prog()).unwrap();
+
+        assert_eq!(buffered, seeked.into_inner());
+    }
+
+    #[test]
+    fn wtns_private_wire_count_matches_r1cs() {
+        fn build() -> Prog<Bn128Field> {
+            Prog {
+                arguments: vec![],
+                return_count: 1,
+                statements: vec![Statement::Constraint(
+                    LinComb::from(FlatVariable::new(1)).into(),
+                    FlatVariable::public(0).into(),
+                    None,
+                )],
+            }
+        }
+
+        let witness: Witness<Bn128Field> = Witness(
+            vec![
+                (FlatVariable::public(0), Bn128Field::from(1)),
+                (FlatVariable::new(1), Bn128Field::from(1)),
+                // a directive-only private variable: present in the witness
+                // but never referenced by a constraint, so it must not be
+                // counted as a wire in either file
+                (FlatVariable::new(2), Bn128Field::from(42)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut wtns_buf = Vec::new();
+        write_wtns(&mut wtns_buf, &build(), &witness).unwrap();
+
+        // witness length sits right after the header section's field_size
+        // (4 bytes) and modulus (32 bytes), themselves 24 bytes into the file
+        let witness_len = u64::from_le_bytes(wtns_buf[60..68].try_into().unwrap());
+
+        let mut r1cs_buf = Vec::new();
+        let wire2label = write_r1cs_with_map(&mut r1cs_buf, build()).unwrap();
+
+        assert_eq!(witness_len, wire2label.len() as u64);
+        assert_eq!(witness_len, 3);
+    }
+
+    #[test]
+    fn wtns_excludes_unreferenced_public_argument() {
+        fn build() -> Prog<Bn128Field> {
+            Prog {
+                arguments: vec![
+                    Parameter {
+                        id: FlatVariable::public(1),
+                        private: false,
+                    },
+                    // declared but never used in a constraint below -- e.g.
+                    // the ZoKrates source took it as a parameter and ignored it
+                    Parameter {
+                        id: FlatVariable::public(2),
+                        private: false,
+                    },
+                ],
+                return_count: 1,
+                statements: vec![Statement::Constraint(
+                    LinComb::from(FlatVariable::public(1)).into(),
+                    FlatVariable::public(0).into(),
+                    None,
+                )],
+            }
+        }
+
+        let witness: Witness<Bn128Field> = Witness(
+            vec![
+                (FlatVariable::public(0), Bn128Field::from(7)),
+                (FlatVariable::public(1), Bn128Field::from(7)),
+                (FlatVariable::public(2), Bn128Field::from(99)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut wtns_buf = Vec::new();
+        write_wtns(&mut wtns_buf, &build(), &witness).unwrap();
+        let witness_len = u64::from_le_bytes(wtns_buf[60..68].try_into().unwrap());
+
+        let mut r1cs_buf = Vec::new();
+        let wire2label = write_r1cs_with_map(&mut r1cs_buf, build()).unwrap();
+
+        assert_eq!(witness_len, wire2label.len() as u64);
+        // ~one, the output, the referenced public input -- not the unreferenced one
+        assert_eq!(witness_len, 3);
+    }
+
+    #[test]
+    fn wtns_values_align_positionally_with_r1cs_wire_labels() {
+        fn encode(v: Bn128Field) -> Vec<u8> {
+            let mut res = vec![0u8; 32];
+            for (value, padded) in v.to_biguint().to_bytes_le().iter().zip(res.iter_mut()) {
+                *padded = *value;
+            }
+            res
+        }
+
+        fn build() -> Prog<Bn128Field> {
+            Prog {
+                arguments: vec![Parameter {
+                    id: FlatVariable::public(1),
+                    private: false,
+                }],
+                return_count: 1,
+                statements: vec![
+                    Statement::Constraint(
+                        LinComb::from(FlatVariable::public(1)).into(),
+                        FlatVariable::public(0).into(),
+                        None,
+                    ),
+                    Statement::Constraint(
+                        LinComb::from(FlatVariable::public(1)).into(),
+                        FlatVariable::new(1).into(),
+                        None,
+                    ),
+                ],
+            }
+        }
+
+        let witness: Witness<Bn128Field> = Witness(
+            vec![
+                (FlatVariable::public(0), Bn128Field::from(2)), // output
+                (FlatVariable::public(1), Bn128Field::from(3)), // public input
+                (FlatVariable::new(1), Bn128Field::from(4)),    // private
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut wtns_buf = Vec::new();
+        write_wtns(&mut wtns_buf, &build(), &witness).unwrap();
+
+        let mut r1cs_buf = Vec::new();
+        let wire2label = write_r1cs_with_map(&mut r1cs_buf, build()).unwrap();
+        assert_eq!(wire2label.len(), 4);
+
+        // the data section's field elements start 80 bytes in: 12
+        // (magic/version/section count) + 12 (header section type/size) + 4
+        // + 32 (field_size, modulus) + 8 (witness length) + 12 (data section
+        // type/size)
+        let data_start = 80;
+        let expected = [
+            encode(Bn128Field::from(1)), // ~one
+            encode(Bn128Field::from(2)), // output
+            encode(Bn128Field::from(3)), // public input
+            encode(Bn128Field::from(4)), // private
+        ];
+        for (i, exp) in expected.iter().enumerate() {
+            let offset = data_start + i * 32;
+            assert_eq!(&wtns_buf[offset..offset + 32], exp.as_slice());
+        }
+    }
 }